@@ -0,0 +1,484 @@
+//! A small framed HTTP/1.1 codec for monoio, in the spirit of tokio's
+//! `Framed` with its `Decoder`/`Encoder` traits but built on monoio's
+//! `AsyncReadRent`/`AsyncWriteRent` owned-buffer model.
+//!
+//! [`HttpRequestDecoder`] owns a growable buffer, feeds everything it has read
+//! to `httparse`, and yields an owned [`http::Request`] once a whole message
+//! (head plus body) is available — retaining any trailing pipelined bytes for
+//! the next call. [`HttpResponseEncoder`] serialises a [`Response`] into a
+//! caller-provided buffer, negotiating compression on the way out.
+
+use bytes::{BufMut, Bytes, BytesMut};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use http::header::{CONTENT_ENCODING, CONTENT_LENGTH, DATE, TRANSFER_ENCODING};
+use http::Response;
+use httparse::{EMPTY_HEADER, Status};
+use httpdate::fmt_http_date;
+use monoio::io::AsyncReadRent;
+use std::fmt;
+use std::io::Write;
+use std::time::SystemTime;
+
+const MAX_HEADERS: usize = 64;
+const BUFFER_SIZE: usize = 8192;
+const MAX_HTTP_MESSAGE_HEADER_SIZE: usize = 8192;
+const MAX_HTTP_MESSAGE_BODY_SIZE: usize = 1024 * 1024;
+// Bodies smaller than this aren't worth compressing: the header overhead and
+// CPU cost outweigh the saving.
+const MIN_COMPRESS_SIZE: usize = 256;
+
+/// Incrementally turn a byte stream into frames.
+pub trait Decoder {
+    type Item;
+    type Error;
+
+    /// Try to pull one frame out of `src`, returning `None` when more bytes are
+    /// needed. Consumed bytes are removed from `src`.
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error>;
+}
+
+/// Serialise a frame into a byte buffer.
+pub trait Encoder<Item> {
+    type Error;
+
+    fn encode(&mut self, item: Item, dst: &mut BytesMut) -> Result<(), Self::Error>;
+}
+
+/// Errors surfaced while decoding a request or encoding a response.
+#[derive(Debug)]
+pub enum HttpError {
+    Io(std::io::Error),
+    /// The request head grew past `MAX_HTTP_MESSAGE_HEADER_SIZE`.
+    HeaderTooLarge,
+    /// The body grew past `MAX_HTTP_MESSAGE_BODY_SIZE`.
+    BodyTooLarge,
+    /// The request or its chunked framing was malformed.
+    Malformed,
+}
+
+impl fmt::Display for HttpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HttpError::Io(e) => write!(f, "io error: {e}"),
+            HttpError::HeaderTooLarge => f.write_str("request header fields too large"),
+            HttpError::BodyTooLarge => f.write_str("payload too large"),
+            HttpError::Malformed => f.write_str("malformed request"),
+        }
+    }
+}
+
+impl std::error::Error for HttpError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            HttpError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for HttpError {
+    fn from(e: std::io::Error) -> Self {
+        HttpError::Io(e)
+    }
+}
+
+/// A framed request reader over an owned, growable buffer.
+pub struct HttpRequestDecoder {
+    buffer: BytesMut,
+}
+
+impl HttpRequestDecoder {
+    pub fn new() -> Self {
+        Self {
+            buffer: BytesMut::with_capacity(BUFFER_SIZE),
+        }
+    }
+
+    /// Read from `stream` until a whole request is available, decode it, and
+    /// return it. Returns `None` when the peer closes the connection cleanly
+    /// between requests. Any pipelined bytes are retained for the next call.
+    pub async fn next<S: AsyncReadRent>(
+        &mut self,
+        stream: &mut S,
+    ) -> Result<Option<http::Request<Bytes>>, HttpError> {
+        loop {
+            let mut buffer = std::mem::take(&mut self.buffer);
+            let decoded = self.decode(&mut buffer);
+            self.buffer = buffer;
+            if let Some(req) = decoded? {
+                return Ok(Some(req));
+            }
+
+            let read_buf = BytesMut::with_capacity(BUFFER_SIZE);
+            let (res, read_buf) = stream.read(read_buf).await;
+            match res {
+                Ok(0) => return Ok(None),
+                Ok(n) => self.buffer.extend_from_slice(&read_buf[..n]),
+                Err(e) => return Err(HttpError::Io(e)),
+            }
+        }
+    }
+}
+
+impl Default for HttpRequestDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder for HttpRequestDecoder {
+    type Item = http::Request<Bytes>;
+    type Error = HttpError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        decode_request(src)
+    }
+}
+
+/// Decode a single request out of `src`, consuming its bytes on success.
+fn decode_request(src: &mut BytesMut) -> Result<Option<http::Request<Bytes>>, HttpError> {
+    let mut headers = [EMPTY_HEADER; MAX_HEADERS];
+    let mut parser = httparse::Request::new(&mut headers);
+
+    let header_len = match parser.parse(src).map_err(|_| HttpError::Malformed)? {
+        Status::Complete(len) => len,
+        Status::Partial => {
+            if src.len() > MAX_HTTP_MESSAGE_HEADER_SIZE {
+                return Err(HttpError::HeaderTooLarge);
+            }
+            return Ok(None);
+        }
+    };
+
+    // Copy the head out so the borrow of `src` can be released before we mutate
+    // it, and so the returned request owns all of its data.
+    let method = parser.method.unwrap_or("").to_owned();
+    let path = parser.path.unwrap_or("/").to_owned();
+    let version = match parser.version {
+        Some(0) => http::Version::HTTP_10,
+        _ => http::Version::HTTP_11,
+    };
+    let mut content_length: Option<usize> = None;
+    let mut chunked = false;
+    let mut has_transfer_encoding = false;
+    let mut owned_headers: Vec<(String, Vec<u8>)> = Vec::with_capacity(parser.headers.len());
+    for header in parser.headers.iter() {
+        if header.name.eq_ignore_ascii_case(CONTENT_LENGTH.as_str()) {
+            let value = std::str::from_utf8(header.value)
+                .ok()
+                .and_then(|v| v.trim().parse::<usize>().ok())
+                .ok_or(HttpError::Malformed)?;
+            // A repeated, conflicting Content-Length is a framing error.
+            if content_length.is_some_and(|existing| existing != value) {
+                return Err(HttpError::Malformed);
+            }
+            content_length = Some(value);
+        } else if header.name.eq_ignore_ascii_case(TRANSFER_ENCODING.as_str()) {
+            has_transfer_encoding = true;
+            chunked = std::str::from_utf8(header.value)
+                .map(|v| v.to_ascii_lowercase().contains("chunked"))
+                .unwrap_or(false);
+        }
+        owned_headers.push((header.name.to_owned(), header.value.to_vec()));
+    }
+
+    // Content-Length alongside Transfer-Encoding is a request-smuggling risk
+    // and must be rejected (RFC 7230 §3.3.3).
+    if content_length.is_some() && has_transfer_encoding {
+        return Err(HttpError::Malformed);
+    }
+
+    // Decide whether the whole body is present yet.
+    let (body, consumed) = if chunked {
+        match decode_chunked(&src[header_len..])? {
+            Some((body, used)) => (body, header_len + used),
+            None => return Ok(None),
+        }
+    } else {
+        let len = content_length.unwrap_or(0);
+        if len > MAX_HTTP_MESSAGE_BODY_SIZE {
+            return Err(HttpError::BodyTooLarge);
+        }
+        if src.len() - header_len < len {
+            return Ok(None);
+        }
+        (
+            Bytes::copy_from_slice(&src[header_len..header_len + len]),
+            header_len + len,
+        )
+    };
+
+    // Drop the consumed message, keeping any following pipelined bytes.
+    let _ = src.split_to(consumed);
+
+    // Rebuild an owned request.
+    let mut builder = http::Request::builder()
+        .method(method.as_str())
+        .uri(path.as_str())
+        .version(version);
+    for (name, value) in &owned_headers {
+        builder = builder.header(name.as_str(), value.as_slice());
+    }
+    builder
+        .body(body)
+        .map(Some)
+        .map_err(|_| HttpError::Malformed)
+}
+
+/// Decode a chunked body out of `input` (the bytes following the head).
+///
+/// Returns the decoded body and how many bytes of `input` it consumed, or
+/// `None` when the stream is not yet complete.
+fn decode_chunked(input: &[u8]) -> Result<Option<(Bytes, usize)>, HttpError> {
+    let mut body = BytesMut::new();
+    let mut offset = 0;
+
+    loop {
+        let rest = &input[offset..];
+        let line_end = match find_crlf(rest) {
+            Some(i) => i,
+            None => {
+                if rest.len() > MAX_HTTP_MESSAGE_HEADER_SIZE {
+                    return Err(HttpError::Malformed);
+                }
+                return Ok(None);
+            }
+        };
+
+        // The size may be followed by chunk extensions after a ';'.
+        let size_field = rest[..line_end]
+            .split(|&b| b == b';')
+            .next()
+            .unwrap_or(&[]);
+        let size = parse_chunk_size(size_field).ok_or(HttpError::Malformed)?;
+        let after_size = offset + line_end + 2;
+
+        if size == 0 {
+            // Consume any trailer lines up to and including the final CRLF.
+            let mut cursor = after_size;
+            loop {
+                match find_crlf(&input[cursor..]) {
+                    Some(0) => return Ok(Some((body.freeze(), cursor + 2))),
+                    Some(i) => cursor += i + 2,
+                    None => {
+                        // Cap the trailer the same way the chunk-size line is
+                        // capped so a non-terminating trailer can't buffer
+                        // unbounded data.
+                        if input.len() - after_size > MAX_HTTP_MESSAGE_HEADER_SIZE {
+                            return Err(HttpError::Malformed);
+                        }
+                        return Ok(None);
+                    }
+                }
+            }
+        }
+
+        // Use checked arithmetic so an astronomically large declared chunk size
+        // can't overflow past the body cap (or panic in a debug build).
+        if body
+            .len()
+            .checked_add(size)
+            .is_none_or(|n| n > MAX_HTTP_MESSAGE_BODY_SIZE)
+        {
+            return Err(HttpError::BodyTooLarge);
+        }
+
+        let chunk_end = match after_size.checked_add(size) {
+            Some(end) => end,
+            None => return Err(HttpError::Malformed),
+        };
+        if input.len() < chunk_end + 2 {
+            return Ok(None);
+        }
+        if &input[chunk_end..chunk_end + 2] != b"\r\n" {
+            return Err(HttpError::Malformed);
+        }
+        body.extend_from_slice(&input[after_size..chunk_end]);
+        offset = chunk_end + 2;
+    }
+}
+
+/// Find the offset of the first CRLF in `buf`, if any.
+fn find_crlf(buf: &[u8]) -> Option<usize> {
+    buf.windows(2).position(|w| w == b"\r\n")
+}
+
+/// Parse a hexadecimal chunk size, rejecting empty or non-hex input.
+fn parse_chunk_size(field: &[u8]) -> Option<usize> {
+    if field.is_empty() {
+        return None;
+    }
+    let mut size: usize = 0;
+    for &b in field {
+        let digit = match b {
+            b'0'..=b'9' => b - b'0',
+            b'a'..=b'f' => b - b'a' + 10,
+            b'A'..=b'F' => b - b'A' + 10,
+            _ => return None,
+        };
+        size = size.checked_mul(16)?.checked_add(digit as usize)?;
+    }
+    Some(size)
+}
+
+/// A response serialiser that negotiates compression against the client's
+/// `Accept-Encoding`.
+pub struct HttpResponseEncoder;
+
+impl HttpResponseEncoder {
+    pub fn new() -> Self {
+        HttpResponseEncoder
+    }
+}
+
+impl Default for HttpResponseEncoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, T: AsRef<[u8]>> Encoder<(&'a Response<T>, Option<&'a str>)> for HttpResponseEncoder {
+    type Error = HttpError;
+
+    fn encode(
+        &mut self,
+        (response, accept_encoding): (&'a Response<T>, Option<&'a str>),
+        dst: &mut BytesMut,
+    ) -> Result<(), Self::Error> {
+        serialize_response(response, accept_encoding, dst);
+        Ok(())
+    }
+}
+
+/// A content coding the server can emit.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Encoding {
+    Br,
+    Gzip,
+    Identity,
+}
+
+impl Encoding {
+    /// The `Content-Encoding` token, or `None` for identity.
+    fn token(self) -> Option<&'static str> {
+        match self {
+            Encoding::Br => Some("br"),
+            Encoding::Gzip => Some("gzip"),
+            Encoding::Identity => None,
+        }
+    }
+}
+
+/// Pick the best supported coding from an `Accept-Encoding` value, preferring
+/// brotli, then gzip, then falling back to identity.
+fn negotiate_encoding(accept_encoding: &str) -> Encoding {
+    let accepts = |coding: &str| {
+        accept_encoding.split(',').any(|part| {
+            let token = part.split(';').next().unwrap_or("").trim();
+            token.eq_ignore_ascii_case(coding) || token == "*"
+        })
+    };
+
+    if accepts("br") {
+        Encoding::Br
+    } else if accepts("gzip") {
+        Encoding::Gzip
+    } else {
+        Encoding::Identity
+    }
+}
+
+fn gzip(body: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(body).expect("gzip into a Vec cannot fail");
+    encoder.finish().expect("gzip into a Vec cannot fail")
+}
+
+fn brotli(body: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut writer = brotli::CompressorWriter::new(&mut out, 4096, 5, 22);
+    writer
+        .write_all(body)
+        .expect("brotli into a Vec cannot fail");
+    drop(writer);
+    out
+}
+
+// Optimized response serializer that writes directly into `dst`.
+fn serialize_response<T: AsRef<[u8]>>(
+    response: &Response<T>,
+    accept_encoding: Option<&str>,
+    dst: &mut BytesMut,
+) {
+    let status = response.status();
+    let headers = response.headers();
+    let body = response.body().as_ref();
+
+    // Negotiate a content coding, skipping tiny bodies and responses that
+    // already carry a Content-Encoding.
+    let encoding = match accept_encoding {
+        Some(accept)
+            if body.len() >= MIN_COMPRESS_SIZE && !headers.contains_key(CONTENT_ENCODING) =>
+        {
+            negotiate_encoding(accept)
+        }
+        _ => Encoding::Identity,
+    };
+    let compressed = match encoding {
+        Encoding::Br => Some(brotli(body)),
+        Encoding::Gzip => Some(gzip(body)),
+        Encoding::Identity => None,
+    };
+    let body = compressed.as_deref().unwrap_or(body);
+
+    // Pre-allocate a reasonable buffer size
+    // Status line + headers + body + some extra space
+    dst.reserve(128 + (headers.len() * 32) + body.len());
+
+    // Write status line
+    dst.put_slice(b"HTTP/1.1 ");
+    dst.put_slice(status.as_u16().to_string().as_bytes());
+    dst.put_slice(b" ");
+    dst.put_slice(status.canonical_reason().unwrap_or("").as_bytes());
+    dst.put_slice(b"\r\n");
+
+    // Add headers
+    for (name, value) in headers.iter() {
+        dst.put_slice(name.as_str().as_bytes());
+        dst.put_slice(b": ");
+        dst.put_slice(value.as_bytes());
+        dst.put_slice(b"\r\n");
+    }
+
+    // Advertise the chosen coding.
+    if let Some(token) = encoding.token() {
+        dst.put_slice(CONTENT_ENCODING.as_str().as_bytes());
+        dst.put_slice(b": ");
+        dst.put_slice(token.as_bytes());
+        dst.put_slice(b"\r\n");
+    }
+
+    // Add Content-Length if not present
+    if !headers.contains_key(CONTENT_LENGTH) {
+        dst.put_slice(CONTENT_LENGTH.as_str().as_bytes());
+        dst.put_slice(b": ");
+        dst.put_slice(body.len().to_string().as_bytes());
+        dst.put_slice(b"\r\n");
+    }
+
+    // Add Date if not present
+    if !headers.contains_key(DATE) {
+        let now = SystemTime::now();
+        dst.put_slice(DATE.as_str().as_bytes());
+        dst.put_slice(b": ");
+        dst.put_slice(fmt_http_date(now).as_bytes());
+        dst.put_slice(b"\r\n");
+    }
+
+    // Finish headers
+    dst.put_slice(b"\r\n");
+
+    // Add body
+    dst.put_slice(body);
+}
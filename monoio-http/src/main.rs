@@ -1,24 +1,51 @@
-use bytes::{BufMut, BytesMut};
-use http::header::{CONTENT_LENGTH, CONTENT_TYPE, DATE};
-use http::{Response, StatusCode};
-use httparse::{EMPTY_HEADER, Status};
-use httpdate::fmt_http_date;
-use monoio::io::{AsyncReadRent, AsyncWriteRentExt};
-use monoio::net::{TcpListener, TcpStream};
+mod codec;
+#[cfg(feature = "tls")]
+mod tls;
+
+use bytes::{Bytes, BytesMut};
+use codec::{Encoder, HttpError, HttpRequestDecoder, HttpResponseEncoder};
+use http::header::{ACCEPT_ENCODING, CONNECTION, CONTENT_TYPE};
+use http::{HeaderValue, Response, StatusCode};
+use monoio::io::{AsyncReadRent, AsyncWriteRent, AsyncWriteRentExt};
+use monoio::net::TcpListener;
+use monoio::time::timeout;
 use std::error::Error;
 use std::net::SocketAddr;
-use std::time::SystemTime;
+use std::time::Duration;
 
-const MAX_HEADERS: usize = 64;
-const BUFFER_SIZE: usize = 8192;
+// Close an otherwise idle connection if the next request (head and body)
+// doesn't arrive in full within this window, so half-open peers don't leak
+// file descriptors.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(30);
 
-#[monoio::main]
+#[monoio::main(timer_enabled = true)]
 async fn main() -> Result<(), Box<dyn Error>> {
     // Define the socket address
     let addr: SocketAddr = ([127, 0, 0, 1], 8080).into();
 
     // Create a TCP listener
     let listener = TcpListener::bind(addr)?;
+
+    // Terminate TLS when it's both compiled in and configured; otherwise serve
+    // plaintext so the benchmark path stays zero-overhead.
+    #[cfg(feature = "tls")]
+    if let Some(acceptor) = tls::acceptor_from_env()? {
+        println!("Listening on https://{addr}");
+        loop {
+            let (stream, _) = listener.accept().await?;
+            let acceptor = acceptor.clone();
+            monoio::spawn(async move {
+                // Run the handshake on the runtime before serving requests.
+                match acceptor.accept(stream).await {
+                    Ok(stream) => {
+                        let _ = handle_connection(stream).await;
+                    }
+                    Err(e) => eprintln!("TLS handshake failed: {e}"),
+                }
+            });
+        }
+    }
+
     println!("Listening on http://{addr}");
 
     // Accept connections and process them
@@ -28,136 +55,130 @@ async fn main() -> Result<(), Box<dyn Error>> {
     }
 }
 
-async fn handle_connection(mut stream: TcpStream) -> Result<(), Box<dyn Error>> {
-    let mut buffer = BytesMut::with_capacity(BUFFER_SIZE);
+async fn handle_connection<S>(mut stream: S) -> Result<(), Box<dyn Error>>
+where
+    S: AsyncReadRent + AsyncWriteRent,
+{
+    let mut decoder = HttpRequestDecoder::new();
+    let mut encoder = HttpResponseEncoder::new();
 
-    // Keep reading from the connection until it's closed
+    // Pull framed requests off the connection until it closes or errors. The
+    // decoder retains any pipelined bytes between iterations.
     loop {
-        // Read the request
-        buffer.clear();
-        let (res, buf) = stream.read(buffer).await;
-        buffer = buf;
-
-        match res {
-            Ok(0) => return Ok(()), // Connection closed
-            Ok(bytes_read) => {
-                // Process the HTTP request
-                let mut headers = [EMPTY_HEADER; MAX_HEADERS];
-                let mut req = httparse::Request::new(&mut headers);
-
-                match req.parse(&buffer[..bytes_read]) {
-                    Ok(Status::Complete(_)) => {
-                        // Create and send response
-                        let res = handle_request(req).await?;
-
-                        // Serialize response
-                        let response_bytes = serialize_response(&res);
-
-                        // Write response
-                        if let Err(e) = stream.write_all(response_bytes).await.0 {
-                            return Err(e.into());
-                        }
-                    }
-                    Ok(Status::Partial) => {
-                        // Handle incomplete request - in a real server you might wait for more data
-                        // For simplicity in benchmark, treat as error
-                        let response = Response::builder()
-                            .status(StatusCode::BAD_REQUEST)
-                            .header(CONTENT_TYPE, "text/plain")
-                            .body("Incomplete HTTP request")?;
-
-                        let response_bytes = serialize_response(&response);
-                        stream.write_all(response_bytes).await.0?;
-                        return Ok(());
-                    }
-                    Err(_) => {
-                        // Handle parsing error
-                        let response = Response::builder()
-                            .status(StatusCode::BAD_REQUEST)
-                            .header(CONTENT_TYPE, "text/plain")
-                            .body("Bad Request")?;
-
-                        let response_bytes = serialize_response(&response);
-                        stream.write_all(response_bytes).await.0?;
-                        return Ok(());
-                    }
-                }
+        let req = match timeout(IDLE_TIMEOUT, decoder.next(&mut stream)).await {
+            // No complete request within the idle window: close quietly.
+            Err(_) => return Ok(()),
+            Ok(Ok(Some(req))) => req,
+            Ok(Ok(None)) => return Ok(()),
+            // Transport failures propagate; protocol errors get a response.
+            Ok(Err(HttpError::Io(e))) => return Err(e.into()),
+            Ok(Err(e)) => {
+                let response = error_response(&e);
+                let mut out = BytesMut::new();
+                encoder.encode((&response, None), &mut out)?;
+                stream.write_all(out).await.0?;
+                return Ok(());
+            }
+        };
+
+        // Honour the client's connection-reuse intent.
+        let persistent = is_persistent(&req);
+
+        let accept_encoding = req
+            .headers()
+            .get(ACCEPT_ENCODING)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_owned());
+
+        // Create response and echo back the negotiated persistence.
+        let mut res = handle_request(&req).await?;
+        res.headers_mut().insert(
+            CONNECTION,
+            HeaderValue::from_static(if persistent { "keep-alive" } else { "close" }),
+        );
+
+        let mut out = BytesMut::new();
+        encoder.encode((&res, accept_encoding.as_deref()), &mut out)?;
+
+        // Write response
+        if let Err(e) = stream.write_all(out).await.0 {
+            return Err(e.into());
+        }
+
+        // A non-persistent request means we close after responding.
+        if !persistent {
+            let _ = stream.flush().await;
+            return Ok(());
+        }
+    }
+}
+
+/// Decide whether the connection should be kept alive after this request,
+/// following the version's default and the `Connection` header.
+fn is_persistent(req: &http::Request<Bytes>) -> bool {
+    let mut close = false;
+    let mut keep_alive = false;
+    if let Some(value) = req.headers().get(CONNECTION).and_then(|v| v.to_str().ok()) {
+        for token in value.split(',') {
+            let token = token.trim();
+            if token.eq_ignore_ascii_case("close") {
+                close = true;
+            } else if token.eq_ignore_ascii_case("keep-alive") {
+                keep_alive = true;
             }
-            Err(e) => return Err(e.into()),
         }
     }
+
+    match req.version() {
+        // HTTP/1.0 defaults to closing unless the client opts into keep-alive.
+        http::Version::HTTP_10 => keep_alive && !close,
+        // HTTP/1.1 (and later) defaults to persistent unless told to close.
+        _ => !close,
+    }
 }
 
-async fn handle_request<'a>(
-    req: httparse::Request<'a, 'a>,
-) -> Result<Response<&'a str>, Box<dyn Error>> {
-    // Create a response based on the path
-    let res = match req.path.expect("the request is complete") {
-        "/" => Response::builder()
+async fn handle_request(req: &http::Request<Bytes>) -> Result<Response<Bytes>, Box<dyn Error>> {
+    // Create a response based on the method and path
+    let res = match (req.method().as_str(), req.uri().path()) {
+        (_, "/") => Response::builder()
             .status(StatusCode::OK)
             .header(CONTENT_TYPE, "text/plain")
-            .body("Hello, World!")?,
-        "/health" => Response::builder()
+            .body(Bytes::from_static(b"Hello, World!"))?,
+        (_, "/health") => Response::builder()
             .status(StatusCode::OK)
             .header(CONTENT_TYPE, "text/plain")
-            .body("Ok")?,
+            .body(Bytes::from_static(b"Ok"))?,
+        ("POST" | "PUT", "/echo") => Response::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, "application/octet-stream")
+            .body(req.body().clone())?,
+        (_, "/large") => Response::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, "text/plain")
+            .body(Bytes::from("Monoio serves this on io_uring. ".repeat(128)))?,
         _ => Response::builder()
             .status(StatusCode::NOT_FOUND)
             .header(CONTENT_TYPE, "text/plain")
-            .body("Not Found")?,
+            .body(Bytes::from_static(b"Not Found"))?,
     };
 
     Ok(res)
 }
 
-// Optimized response serializer that returns bytes directly
-fn serialize_response<T: AsRef<[u8]>>(response: &Response<T>) -> BytesMut {
-    let status = response.status();
-    let headers = response.headers();
-    let body = response.body().as_ref();
-
-    // Pre-allocate a reasonable buffer size
-    // Status line + headers + body + some extra space
-    let capacity = 128 + (headers.len() * 32) + body.len();
-    let mut buffer = BytesMut::with_capacity(capacity);
-
-    // Write status line
-    buffer.put_slice(b"HTTP/1.1 ");
-    buffer.put_slice(status.as_u16().to_string().as_bytes());
-    buffer.put_slice(b" ");
-    buffer.put_slice(status.canonical_reason().unwrap_or("").as_bytes());
-    buffer.put_slice(b"\r\n");
-
-    // Add headers
-    for (name, value) in headers.iter() {
-        buffer.put_slice(name.as_str().as_bytes());
-        buffer.put_slice(b": ");
-        buffer.put_slice(value.as_bytes());
-        buffer.put_slice(b"\r\n");
-    }
-
-    // Add Content-Length if not present
-    if !headers.contains_key(CONTENT_LENGTH) {
-        buffer.put_slice(CONTENT_LENGTH.as_str().as_bytes());
-        buffer.put_slice(b": ");
-        buffer.put_slice(body.len().to_string().as_bytes());
-        buffer.put_slice(b"\r\n");
-    }
-
-    // Add Date if not present
-    if !headers.contains_key(DATE) {
-        let now = SystemTime::now();
-        buffer.put_slice(DATE.as_str().as_bytes());
-        buffer.put_slice(b": ");
-        buffer.put_slice(fmt_http_date(now).as_bytes());
-        buffer.put_slice(b"\r\n");
-    }
-
-    // Finish headers
-    buffer.put_slice(b"\r\n");
-
-    // Add body
-    buffer.put_slice(body);
+/// Map a decode error to the response the client should see.
+fn error_response(err: &HttpError) -> Response<&'static str> {
+    let (status, body) = match err {
+        HttpError::HeaderTooLarge => (
+            StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE,
+            "Request Header Fields Too Large",
+        ),
+        HttpError::BodyTooLarge => (StatusCode::PAYLOAD_TOO_LARGE, "Payload Too Large"),
+        _ => (StatusCode::BAD_REQUEST, "Bad Request"),
+    };
 
-    buffer
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "text/plain")
+        .body(body)
+        .expect("static error response is always valid")
 }
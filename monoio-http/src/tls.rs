@@ -0,0 +1,46 @@
+//! TLS termination for the monoio server, built on `monoio-rustls`.
+//!
+//! Enabled by the `tls` cargo feature and activated at runtime when the
+//! `MONOIO_HTTP_TLS_CERT`/`MONOIO_HTTP_TLS_KEY` PEM paths are set.
+
+use monoio_rustls::TlsAcceptor;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::ServerConfig;
+use std::error::Error;
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::Arc;
+
+/// Build a TLS acceptor from the cert/key PEM paths in the environment, or
+/// `None` when TLS isn't configured.
+pub fn acceptor_from_env() -> Result<Option<TlsAcceptor>, Box<dyn Error>> {
+    let (cert_path, key_path) = match (
+        std::env::var("MONOIO_HTTP_TLS_CERT"),
+        std::env::var("MONOIO_HTTP_TLS_KEY"),
+    ) {
+        (Ok(cert), Ok(key)) => (cert, key),
+        _ => return Ok(None),
+    };
+
+    let certs = load_certs(&cert_path)?;
+    let key = load_key(&key_path)?;
+
+    let config = ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)?;
+
+    Ok(Some(TlsAcceptor::from(Arc::new(config))))
+}
+
+fn load_certs(path: &str) -> Result<Vec<CertificateDer<'static>>, Box<dyn Error>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let certs = rustls_pemfile::certs(&mut reader).collect::<Result<Vec<_>, _>>()?;
+    Ok(certs)
+}
+
+fn load_key(path: &str) -> Result<PrivateKeyDer<'static>, Box<dyn Error>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let key = rustls_pemfile::private_key(&mut reader)?
+        .ok_or("no private key found in PEM file")?;
+    Ok(key)
+}